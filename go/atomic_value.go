@@ -2,6 +2,7 @@ package utils
 
 import (
 	"errors"
+	"sync"
 	"sync/atomic"
 )
 
@@ -90,3 +91,217 @@ func (a *AValue[T]) CompareAndSwap(oldV, newV T) bool {
 func (a *AValue[T]) StoreIfEmpty(t T) bool {
 	return a.v.CompareAndSwap(nil, t)
 }
+
+// Update loads the current value and passes it to f (ok is false if no value
+// has been stored), storing the value f returns using a compare-and-swap,
+// retrying with the newly loaded value until the swap succeeds. If f returns
+// false, nothing is stored and Update returns. Returns the value that was
+// replaced (passed to the final call of f) and whether a value was stored. As
+// with CompareAndSwap, T must be comparable, otherwise this will panic.
+func (a *AValue[T]) Update(
+	f func(old T, ok bool) (T, bool),
+) (old T, updated bool) {
+	for {
+		var t T
+		oldV := a.v.Load()
+		if oldV != nil {
+			t = oldV.(T)
+		}
+		newT, store := f(t, oldV != nil)
+		if !store {
+			return t, false
+		}
+		if a.v.CompareAndSwap(oldV, newT) {
+			return t, true
+		}
+	}
+}
+
+// StoreIf stores the value if pred returns true when passed the current value
+// (ok is false if no value has been stored), retrying if the value changes
+// between the check and the store. Returns true if stored. T must be
+// comparable (see Update).
+func (a *AValue[T]) StoreIf(t T, pred func(old T, ok bool) bool) bool {
+	_, stored := a.Update(func(old T, ok bool) (T, bool) {
+		return t, pred(old, ok)
+	})
+	return stored
+}
+
+// SwapIf is the same as StoreIf except it also returns the replaced value. If
+// there was no value stored, old is the default value.
+func (a *AValue[T]) SwapIf(
+	t T, pred func(old T, ok bool) bool,
+) (old T, swapped bool) {
+	return a.Update(func(old T, ok bool) (T, bool) {
+		return t, pred(old, ok)
+	})
+}
+
+// VersionedAValue is an atomic value that keeps a generation counter which
+// is incremented on every store, allowing readers to cheaply check whether the
+// value has changed since it was last loaded without comparing values. The
+// first stored value has generation 1; 0 means nothing has been stored.
+type VersionedAValue[T any] struct {
+	v AValue[*versioned[T]]
+}
+
+type versioned[T any] struct {
+	val T
+	gen uint64
+}
+
+// NewVersionedAValue constructs a new VersionedAValue with the given value
+// stored as generation 1. If no initial value is desired, create using struct
+// literal (&VersionedAValue{}).
+func NewVersionedAValue[T any](t T) *VersionedAValue[T] {
+	va := &VersionedAValue[T]{}
+	va.Store(t)
+	return va
+}
+
+// Load loads the value along with its generation. The default value and 0
+// are returned if there was no value stored.
+func (va *VersionedAValue[T]) Load() (t T, gen uint64) {
+	if v, ok := va.v.LoadSafe(); ok {
+		t, gen = v.val, v.gen
+	}
+	return
+}
+
+// Generation returns the current generation, or 0 if there was no value
+// stored.
+func (va *VersionedAValue[T]) Generation() uint64 {
+	_, gen := va.Load()
+	return gen
+}
+
+// Store stores a value, returning its generation.
+func (va *VersionedAValue[T]) Store(t T) uint64 {
+	var gen uint64
+	va.v.Update(func(old *versioned[T], ok bool) (*versioned[T], bool) {
+		gen = 1
+		if ok {
+			gen = old.gen + 1
+		}
+		return &versioned[T]{val: t, gen: gen}, true
+	})
+	return gen
+}
+
+// ALazy is an atomic cell that is initialized at most once. Loads after
+// initialization are lock-free. The zero value is ready to use and must not be
+// copied after first use.
+type ALazy[T any] struct {
+	v   AValue[T]
+	mtx sync.Mutex
+}
+
+// Get returns the value and true if the cell has been initialized, otherwise,
+// returns the default value and false.
+func (l *ALazy[T]) Get() (T, bool) {
+	return l.v.LoadSafe()
+}
+
+// GetOrInit returns the value, initializing it with f if it hasn't been
+// initialized. If multiple goroutines call this concurrently, f is only called
+// once and the others wait for and return its result.
+func (l *ALazy[T]) GetOrInit(f func() T) T {
+	t, _ := l.GetOrTryInit(func() (T, error) {
+		return f(), nil
+	})
+	return t
+}
+
+// GetOrTryInit is the same as GetOrInit except f may fail. If f returns an
+// error, the cell is left uninitialized and the error is returned.
+func (l *ALazy[T]) GetOrTryInit(f func() (T, error)) (T, error) {
+	if t, ok := l.v.LoadSafe(); ok {
+		return t, nil
+	}
+	l.mtx.Lock()
+	defer l.mtx.Unlock()
+	if t, ok := l.v.LoadSafe(); ok {
+		return t, nil
+	}
+	t, err := f()
+	if err != nil {
+		return t, err
+	}
+	l.v.Store(t)
+	return t, nil
+}
+
+// DoubleBuffered holds a front value, which readers load atomically, and a
+// back value, which writers mutate under a lock and publish as the new front.
+// Readers always see a consistent value, never one being written to.
+type DoubleBuffered[T any] struct {
+	front AValue[*T]
+	back  *Mutex[T]
+	clone func(T) T
+}
+
+// NewDoubleBuffered constructs a new DoubleBuffered with the given value
+// published as the front and a clone of it as the back. Clone is used to copy
+// the back value on each publish so writers never mutate data shared with the
+// front. If clone is nil, values are shallow copied, which is only sufficient
+// if T holds no references (e.g., slices, maps, or pointers) that get mutated.
+func NewDoubleBuffered[T any](t T, clone func(T) T) *DoubleBuffered[T] {
+	if clone == nil {
+		clone = func(t T) T { return t }
+	}
+	db := &DoubleBuffered[T]{back: NewMutex(clone(t)), clone: clone}
+	db.front.Store(NewT(t))
+	return db
+}
+
+// Load returns a pointer to the front value. The data should not be mutated.
+func (db *DoubleBuffered[T]) Load() *T {
+	return db.front.Load()
+}
+
+// Write locks the back value and calls the passed function with a pointer to
+// it. Changes are not visible to readers until Publish is called.
+func (db *DoubleBuffered[T]) Write(f func(*T)) {
+	db.back.Apply(f)
+}
+
+// Publish makes the back value the new front, replacing the back value with a
+// clone of it.
+func (db *DoubleBuffered[T]) Publish() {
+	db.back.Apply(db.publish)
+}
+
+// WriteAndPublish is the same as Write followed by Publish except the lock is
+// held for both, so no other writes can be published along with f's.
+func (db *DoubleBuffered[T]) WriteAndPublish(f func(*T)) {
+	db.back.Apply(func(back *T) {
+		f(back)
+		db.publish(back)
+	})
+}
+
+func (db *DoubleBuffered[T]) publish(back *T) {
+	cloned := db.clone(*back)
+	db.front.Store(NewT(*back))
+	*back = cloned
+}
+
+// cachePadSize is the padding placed on each side of a CachePadded value. 128
+// bytes covers the cache line size (or adjacent line prefetching) of common
+// architectures.
+const cachePadSize = 128
+
+// CachePadded pads a value so that it doesn't share a cache line with any
+// surrounding data, preventing false sharing when, for example, a slice of
+// AValues is used as per-core slots. Since Go doesn't allow specifying
+// alignment, padding is placed on both sides of the value. Must not be copied
+// if T must not be copied (e.g., AValue).
+type CachePadded[T any] struct {
+	_ [cachePadSize]byte
+
+	// Value is the padded value.
+	Value T
+
+	_ [cachePadSize]byte
+}